@@ -0,0 +1,208 @@
+/*!
+The derive macros behind `upnp`'s `#[derive(FromHeaders)]` / `#[derive(ToHeaders)]` framework
+(see `upnp::ssdp::header_traits`). Kept in a companion crate, as `proc-macro = true` crates
+cannot also export ordinary items.
+
+Each field's wire mapping is read from its `#[header(...)]` attribute:
+
+* `#[header("NAME")]` — wire header name; defaults to the upper-cased field name.
+* `#[header(required)]` — missing header is an error.
+* `#[header(default)]` — missing header falls back to `Default::default()`.
+* `#[header(rest)]` — collects every unclaimed header into a `HashMap<String, String>` field.
+* `#[header(skip)]` — field is always `Default::default()`, never read from the wire.
+
+On the `ToHeaders` side, an `Option<T>` field is only pushed when it is `Some`; this is how a
+struct models a header that only applies to some spec version without needing its own
+`#[header(...)]` variant.
+*/
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(FromHeaders, attributes(header))]
+pub fn derive_from_headers(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = named_fields(&input.data, "FromHeaders");
+
+    let claimed_names: Vec<String> = fields
+        .iter()
+        .filter_map(|field| {
+            let spec = HeaderSpec::from_attrs(field);
+            if spec.rest || spec.skip {
+                None
+            } else {
+                Some(spec.name)
+            }
+        })
+        .collect();
+
+    let field_inits = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let spec = HeaderSpec::from_attrs(field);
+        let header_name = spec.name;
+
+        if spec.skip {
+            quote! { #ident: ::std::default::Default::default() }
+        } else if spec.rest {
+            let claimed = &claimed_names;
+            quote! {
+                #ident: ::upnp::ssdp::header_traits::rest(headers, &[#(#claimed),*])
+            }
+        } else if header_name.eq_ignore_ascii_case("CACHE-CONTROL") {
+            if spec.required {
+                quote! {
+                    #ident: ::upnp::ssdp::header_traits::max_age(headers, #header_name)?
+                }
+            } else {
+                quote! {
+                    #ident: ::upnp::ssdp::header_traits::max_age_opt(headers, #header_name)?
+                        .unwrap_or_default()
+                }
+            }
+        } else if spec.required {
+            quote! {
+                #ident: ::upnp::ssdp::header_traits::required(headers, #header_name)?
+            }
+        } else {
+            quote! {
+                #ident: ::upnp::ssdp::header_traits::with_default(headers, #header_name)
+            }
+        }
+    });
+
+    let expanded = quote! {
+        impl ::upnp::ssdp::header_traits::FromHeaders for #name {
+            fn from_headers(
+                headers: &::std::collections::HashMap<String, String>,
+            ) -> ::std::result::Result<Self, ::upnp::Error> {
+                Ok(#name { #(#field_inits),* })
+            }
+        }
+    };
+    expanded.into()
+}
+
+#[proc_macro_derive(ToHeaders, attributes(header))]
+pub fn derive_to_headers(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = named_fields(&input.data, "ToHeaders");
+
+    let pushes = fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let spec = HeaderSpec::from_attrs(field);
+        if spec.skip || spec.rest {
+            None
+        } else {
+            let header_name = spec.name;
+            if header_name.eq_ignore_ascii_case("CACHE-CONTROL") {
+                // The field holds the parsed `max-age` seconds, not the literal directive
+                // string `FromHeaders` reads it back out of; re-wrap it on the way out too.
+                Some(quote! {
+                    builder.add_header(#header_name, &format!("max-age={}", self.#ident));
+                })
+            } else if is_option(&field.ty) {
+                // Only push the header when the field is actually set, so a struct can model
+                // a header that only applies to some spec version without its own attribute.
+                Some(quote! {
+                    if let Some(value) = &self.#ident {
+                        builder.add_header(#header_name, &value.to_string());
+                    }
+                })
+            } else {
+                Some(quote! {
+                    builder.add_header(#header_name, &self.#ident.to_string());
+                })
+            }
+        }
+    });
+
+    let expanded = quote! {
+        impl ::upnp::ssdp::header_traits::ToHeaders for #name {
+            fn to_headers(&self, builder: &mut ::upnp::httpu::RequestBuilder) {
+                #(#pushes)*
+            }
+        }
+    };
+    expanded.into()
+}
+
+/// Whether `ty` is (textually) an `Option<...>`, used to let `ToHeaders` skip a field rather
+/// than push an empty header when it is `None`.
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "Option")
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn named_fields(data: &Data, derive_name: &str) -> &syn::punctuated::Punctuated<syn::Field, syn::Token![,]> {
+    match data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("{} only supports structs with named fields", derive_name),
+        },
+        _ => panic!("{} can only be derived for structs", derive_name),
+    }
+}
+
+/// The parsed contents of a field's `#[header(...)]` attribute(s).
+struct HeaderSpec {
+    name: String,
+    required: bool,
+    rest: bool,
+    skip: bool,
+}
+
+impl HeaderSpec {
+    fn from_attrs(field: &syn::Field) -> Self {
+        let mut spec = HeaderSpec {
+            name: field
+                .ident
+                .as_ref()
+                .map(|ident| ident.to_string().to_uppercase())
+                .unwrap_or_default(),
+            required: false,
+            rest: false,
+            skip: false,
+        };
+        for attr in &field.attrs {
+            if !attr.path.is_ident("header") {
+                continue;
+            }
+            let meta = match attr.parse_meta() {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            if let Meta::List(list) = meta {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Lit(Lit::Str(s)) => spec.name = s.value(),
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("required") => {
+                            spec.required = true
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                            spec.required = false
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("rest") => {
+                            spec.rest = true
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                            spec.skip = true
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        spec
+    }
+}
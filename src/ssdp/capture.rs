@@ -0,0 +1,150 @@
+/*!
+An opt-in pcapng writer backing
+[`Options::capture_path`](../search/struct.Options.html#structfield.capture_path): every sent
+M-SEARCH and every received reply/advertisement is appended as a synthetic IPv4/UDP packet,
+so the resulting file opens directly in Wireshark. This exists purely to let a user share a
+capture alongside an interop bug report; it has no effect on anything the crate actually
+sends or parses.
+*/
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddrV4;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `LINKTYPE_RAW` (101) — no link-layer header, just the IPv4 packet.
+const LINKTYPE_RAW: u16 = 101;
+
+const BLOCK_TYPE_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const BLOCK_TYPE_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_TYPE_ENHANCED_PACKET: u32 = 0x0000_0006;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Appends captured datagrams to a pcapng file, writing the Section Header Block and
+/// Interface Description Block up front the first time the file is created.
+///
+pub struct Capture {
+    file: File,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Capture {
+    /// Open (creating if needed) the pcapng file at `path`, writing its file-level blocks the
+    /// first time it is created; an existing file is appended to as-is.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let is_new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if is_new {
+            write_section_header_block(&mut file)?;
+            write_interface_description_block(&mut file)?;
+        }
+        Ok(Capture { file })
+    }
+
+    /// Append one UDP datagram whose payload is exactly `payload`, synthesizing the IPv4/UDP
+    /// headers around it from the real `source`/`destination` so the capture shows what was
+    /// actually sent or received.
+    pub fn write_datagram(
+        &mut self,
+        source: SocketAddrV4,
+        destination: SocketAddrV4,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let packet = synthesize_ipv4_udp(source, destination, payload);
+        write_enhanced_packet_block(&mut self.file, &packet)
+    }
+}
+
+/// Reconstruct an HTTP-like status line and header block for `headers`, for capturing an
+/// inbound datagram whose raw bytes [`SsdpCodec`](../codec/struct.SsdpCodec.html) does not
+/// retain past decoding; not guaranteed to be byte-identical to what was received, but
+/// equivalent for the purpose of inspecting headers in Wireshark.
+pub fn render_headers(status_line: &str, headers: &HashMap<String, String>) -> Vec<u8> {
+    let mut text = format!("{}\r\n", status_line);
+    for (name, value) in headers {
+        text.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    text.push_str("\r\n");
+    text.into_bytes()
+}
+
+fn write_section_header_block(file: &mut File) -> io::Result<()> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&0x1A2B_3C4Du32.to_le_bytes()); // byte-order magic
+    body.extend_from_slice(&1u16.to_le_bytes()); // major version
+    body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+    body.extend_from_slice(&(-1i64).to_le_bytes()); // section length, unknown
+    write_block(file, BLOCK_TYPE_SECTION_HEADER, &body)
+}
+
+fn write_interface_description_block(file: &mut File) -> io::Result<()> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    body.extend_from_slice(&65535u32.to_le_bytes()); // snap length
+    write_block(file, BLOCK_TYPE_INTERFACE_DESCRIPTION, &body)
+}
+
+fn write_enhanced_packet_block(file: &mut File, packet: &[u8]) -> io::Result<()> {
+    let micros = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let mut body = Vec::new();
+    body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+    body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes()); // timestamp (high)
+    body.extend_from_slice(&(micros as u32).to_le_bytes()); // timestamp (low)
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // captured length
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // original length
+    body.extend_from_slice(packet);
+    while body.len() % 4 != 0 {
+        body.push(0); // pcapng pads every block body to a 32-bit boundary
+    }
+    write_block(file, BLOCK_TYPE_ENHANCED_PACKET, &body)
+}
+
+/// Every pcapng block shares this framing: type, total length (repeated after the body so a
+/// reader can walk the file backwards), body.
+fn write_block(file: &mut File, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total_length = (12 + body.len()) as u32;
+    file.write_all(&block_type.to_le_bytes())?;
+    file.write_all(&total_length.to_le_bytes())?;
+    file.write_all(body)?;
+    file.write_all(&total_length.to_le_bytes())
+}
+
+/// Wrap `payload` in synthetic IPv4 + UDP headers using the real source/destination
+/// addresses; checksums are left as zero (optional for IPv4/UDP) since nothing in this crate
+/// ever re-reads the capture.
+fn synthesize_ipv4_udp(source: SocketAddrV4, destination: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    let udp_length = 8 + payload.len();
+    let mut udp = Vec::with_capacity(udp_length);
+    udp.extend_from_slice(&source.port().to_be_bytes());
+    udp.extend_from_slice(&destination.port().to_be_bytes());
+    udp.extend_from_slice(&(udp_length as u16).to_be_bytes());
+    udp.extend_from_slice(&0u16.to_be_bytes()); // checksum
+    udp.extend_from_slice(payload);
+
+    let total_length = 20 + udp_length;
+    let mut packet = Vec::with_capacity(total_length);
+    packet.push(0x45); // version 4, 5 x 32-bit words of header, no options
+    packet.push(0); // DSCP/ECN
+    packet.extend_from_slice(&(total_length as u16).to_be_bytes());
+    packet.extend_from_slice(&0u16.to_be_bytes()); // identification
+    packet.extend_from_slice(&0u16.to_be_bytes()); // flags/fragment offset
+    packet.push(64); // TTL
+    packet.push(17); // protocol: UDP
+    packet.extend_from_slice(&0u16.to_be_bytes()); // header checksum
+    packet.extend_from_slice(&source.ip().octets());
+    packet.extend_from_slice(&destination.ip().octets());
+    packet.extend_from_slice(&udp);
+    packet
+}
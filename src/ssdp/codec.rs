@@ -0,0 +1,60 @@
+/*!
+A `tokio_util` codec that turns the raw datagrams read off the multicast socket into
+[`MulticastResponse`](../../httpu/struct.Response.html) values.
+
+This is used by [`search_stream`](../search/fn.search_stream.html) and by the passive
+advertisement listener [`listen`](../listen/fn.listen.html) to decode each UDP packet
+independently; SSDP has no framing
+beyond "one packet is one message" so the decoder never needs to buffer across calls.
+*/
+use crate::httpu::Response as MulticastResponse;
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::io;
+use tokio_util::codec::Decoder;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Decodes a single HTTP-over-UDP datagram (an M-SEARCH reply or a `NOTIFY` request) into its
+/// headers, exposed as a [`MulticastResponse`].
+///
+/// Each call to `UdpSocket::recv_from` returns exactly one datagram, so `decode` always
+/// consumes the whole buffer it is given rather than waiting for more input.
+///
+#[derive(Debug, Default)]
+pub struct SsdpCodec;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Decoder for SsdpCodec {
+    type Item = MulticastResponse;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        let datagram = src.split_to(src.len());
+        let text = String::from_utf8_lossy(&datagram);
+        // The first line is the HTTP status/request line (`HTTP/1.1 200 OK` or
+        // `NOTIFY * HTTP/1.1`); SSDP only cares about the headers that follow it.
+        let mut lines = text.split("\r\n");
+        let _ = lines.next();
+
+        let mut headers = HashMap::new();
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_uppercase(), value.trim().to_string());
+            }
+        }
+        Ok(Some(MulticastResponse::new(headers)))
+    }
+}
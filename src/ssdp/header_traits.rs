@@ -0,0 +1,126 @@
+/*!
+A small framework that lets SSDP message types declare their wire mapping with field
+attributes instead of hand-parsing a `HashMap<String, String>` of headers one header at a
+time.
+
+`#[derive(FromHeaders)]` (for inbound messages) and `#[derive(ToHeaders)]` (for outbound
+ones), provided by the companion `rust-upnp-derive` crate, read a `#[header(...)]` attribute
+on every field:
+
+* `#[header("NAME")]` — the wire header name to look up; defaults to the upper-cased field
+  name when omitted.
+* `#[header(required)]` — fail with `Error::MessageFormat(MessageErrorKind::MissingRequiredField)`
+  if the header is absent, instead of silently falling back to a default.
+* `#[header(default)]` — fall back to the field type's `Default::default()` if the header is
+  absent.
+* `#[header(rest)]` — collect every header not claimed by another field (case-insensitively)
+  into this field, which must be a `HashMap<String, String>`.
+* `#[header(skip)]` — do not read this field from the wire at all; it is always
+  `Default::default()`.
+
+Field values are parsed with `FromStr`, so typed headers (e.g. `u64` for `BOOTID.UPNP.ORG`)
+are converted automatically instead of being left as raw strings, and a `#[header("CACHE-CONTROL")]`
+field is special-cased by the derive macro to extract the `max-age=N` component rather than
+the literal directive string.
+*/
+use crate::{Error, MessageErrorKind};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Implemented (usually via `#[derive(FromHeaders)]`) by message types that can be built
+/// from the header map of an inbound message.
+///
+pub trait FromHeaders: Sized {
+    /// Parse `Self` out of the (case-insensitive) `headers` of an inbound message.
+    fn from_headers(headers: &HashMap<String, String>) -> Result<Self, Error>;
+}
+
+///
+/// Implemented (usually via `#[derive(ToHeaders)]`) by message types that can be written out
+/// as a set of wire headers.
+///
+pub trait ToHeaders {
+    /// Push every mapped field of `self` onto an outbound `builder` as a header.
+    fn to_headers(&self, builder: &mut crate::httpu::RequestBuilder);
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+//
+// The functions below are the building blocks the derive macro generates calls to; they are
+// not usually called directly.
+//
+
+/// Case-insensitive lookup, since header names arrive with whatever casing the remote device
+/// chose to send.
+pub fn lookup<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Look up `name`, parse it as `T`, and fail if it is absent or does not parse; backs
+/// `#[header(required)]` fields.
+pub fn required<T: FromStr>(headers: &HashMap<String, String>, name: &str) -> Result<T, Error> {
+    let raw = lookup(headers, name).ok_or_else(|| {
+        error!("required - missing header {}", name);
+        Error::MessageFormat(MessageErrorKind::MissingRequiredField)
+    })?;
+    raw.parse().map_err(|_| {
+        error!("required - could not parse header {} ({})", name, raw);
+        Error::MessageFormat(MessageErrorKind::InvalidFieldValue)
+    })
+}
+
+/// Look up `name` and parse it as `T`, falling back to `T::default()` if it is absent or does
+/// not parse; backs `#[header(default)]` fields.
+pub fn with_default<T: FromStr + Default>(headers: &HashMap<String, String>, name: &str) -> T {
+    lookup(headers, name)
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Extract the `max-age` component of a `CACHE-CONTROL`-style header; backs `required` fields
+/// whose `#[header(...)]` name is `CACHE-CONTROL`.
+pub fn max_age(headers: &HashMap<String, String>, name: &str) -> Result<u64, Error> {
+    lazy_static::lazy_static! {
+        static ref MAX_AGE: regex::Regex = regex::Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap();
+    }
+    let raw = lookup(headers, name).ok_or_else(|| {
+        error!("max_age - missing header {}", name);
+        Error::MessageFormat(MessageErrorKind::MissingRequiredField)
+    })?;
+    let captures = MAX_AGE.captures(raw).ok_or_else(|| {
+        error!("max_age - {} did not match 'max-age=N' ({})", name, raw);
+        Error::MessageFormat(MessageErrorKind::InvalidFieldValue)
+    })?;
+    captures[1]
+        .parse()
+        .map_err(|_| Error::MessageFormat(MessageErrorKind::InvalidFieldValue))
+}
+
+/// As [`max_age`], but `None` rather than an error when `name` is absent; for messages (e.g.
+/// `NTS: ssdp:update`) that are not required by spec to carry a cache lifetime.
+pub fn max_age_opt(headers: &HashMap<String, String>, name: &str) -> Result<Option<u64>, Error> {
+    if lookup(headers, name).is_none() {
+        return Ok(None);
+    }
+    max_age(headers, name).map(Some)
+}
+
+/// Headers not claimed by any other `#[header(...)]` field; backs a struct's `#[header(rest)]`
+/// field.
+pub fn rest(headers: &HashMap<String, String>, claimed: &[&str]) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter(|(key, _)| !claimed.iter().any(|name| key.eq_ignore_ascii_case(name)))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
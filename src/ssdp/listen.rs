@@ -0,0 +1,170 @@
+/*!
+Where `search`/`search_stream` are active — they send an M-SEARCH and wait for replies — this
+module is passive: it joins the SSDP multicast group and listens for the unsolicited `NOTIFY *
+HTTP/1.1` advertisements that well-behaved devices send when they join the network, refresh
+their cache lifetime, or leave.
+
+[`listen`] yields an [`Advertisement`] for every `NOTIFY` received, so a long-running control
+point can keep a device list current by folding the stream into a cache (see
+[`ResponseCache`](../search/struct.ResponseCache.html)) instead of re-running `search_once` on
+a timer.
+*/
+use crate::ssdp::capture;
+use crate::ssdp::codec::SsdpCodec;
+use crate::ssdp::header_traits;
+use crate::ssdp::protocol;
+use crate::ssdp::search::{interface_address, Options};
+use crate::{Error, MessageErrorKind};
+use futures::stream::{Stream, StreamExt};
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use tokio::net::UdpSocket;
+use tokio_util::udp::UdpFramed;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The fields a `NOTIFY` advertisement carries regardless of its `NTS`; `ssdp:byebye` omits
+/// `LOCATION`/`CACHE-CONTROL` since the device is leaving, so they are not part of this shared
+/// shape and instead live only on [`Advertisement::Alive`] and [`Advertisement::Update`].
+///
+#[derive(Clone, Debug)]
+pub enum Advertisement {
+    /// `NTS: ssdp:alive` — a device/service is present; add it, or refresh it if already known.
+    Alive {
+        /// The advertised `NT` (notification type), e.g. `upnp:rootdevice`.
+        notification_type: String,
+        /// The advertised `USN`, used to identify this entry across advertisements.
+        service_name: String,
+        /// The `LOCATION` of the device/service description.
+        location: String,
+        /// The `max-age` component of `CACHE-CONTROL`, in seconds.
+        max_age: u64,
+        /// The `BOOTID.UPNP.ORG` value; a later advertisement with a higher value means the
+        /// device rebooted.
+        boot_id: u64,
+    },
+    /// `NTS: ssdp:update` — a known device/service's `LOCATION`/`BOOTID.UPNP.ORG` changed.
+    Update {
+        /// The advertised `NT` (notification type).
+        notification_type: String,
+        /// The advertised `USN`.
+        service_name: String,
+        /// The (possibly new) `LOCATION` of the device/service description.
+        location: String,
+        /// The `max-age` component of `CACHE-CONTROL`, if present; `ssdp:update` carries
+        /// `BOOTID`/`NEXTBOOTID`/`SEARCHPORT`, not a cache lifetime, so real devices typically
+        /// omit `CACHE-CONTROL` here and this is `None`.
+        max_age: Option<u64>,
+        /// The (possibly new) `BOOTID.UPNP.ORG` value.
+        boot_id: u64,
+    },
+    /// `NTS: ssdp:byebye` — a device/service is leaving the network; remove it.
+    ByeBye {
+        /// The advertised `NT` (notification type).
+        notification_type: String,
+        /// The advertised `USN`, used to remove the matching entry.
+        service_name: String,
+    },
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Join the SSDP multicast group and return a stream of every `NOTIFY` advertisement received,
+/// parsed into an [`Advertisement`]. Datagrams that cannot be parsed (truncated replies, an
+/// unrecognised `NTS`) are logged and dropped rather than ending the stream.
+///
+/// `options` is reused from `search`/`search_stream` for the network interface to join the
+/// multicast group on; its search-specific fields (`search_target`, `control_point`, ...) are
+/// not used here.
+///
+pub async fn listen(options: Options) -> Result<impl Stream<Item = Advertisement>, Error> {
+    info!("listen - options: {:?}", options);
+    let interface = match &options.network_interface {
+        Some(name) => interface_address(name)?,
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+    let multicast_address: SocketAddrV4 = protocol::MULTICAST_ADDRESS.parse().unwrap();
+    // Bind to `UNSPECIFIED`, not `interface`: on Linux a socket bound to a unicast address never
+    // receives datagrams addressed to the multicast group, so `interface` is only meaningful as
+    // the `join_multicast_v4` argument below.
+    let bind_address = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, multicast_address.port());
+    let socket = UdpSocket::bind(bind_address).await.map_err(Error::from)?;
+    socket
+        .join_multicast_v4(*multicast_address.ip(), interface)
+        .map_err(Error::from)?;
+
+    let mut capture = match &options.capture_path {
+        Some(path) => Some(capture::Capture::open(path).map_err(Error::from)?),
+        None => None,
+    };
+
+    let framed = UdpFramed::new(socket, SsdpCodec::default());
+    Ok(framed.filter_map(move |frame| {
+        let result = match frame {
+            Ok((response, from)) => {
+                if let (Some(capture), std::net::SocketAddr::V4(from)) = (capture.as_mut(), from) {
+                    let rendered = capture::render_headers("NOTIFY * HTTP/1.1", &response.headers);
+                    let _ = capture.write_datagram(from, multicast_address, &rendered);
+                }
+                match Advertisement::try_from(response) {
+                    Ok(advertisement) => Some(advertisement),
+                    Err(e) => {
+                        warn!("listen - discarding malformed NOTIFY: {}", e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("listen - discarding unreadable datagram: {}", e);
+                None
+            }
+        };
+        async move { result }
+    }))
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<crate::httpu::Response> for Advertisement {
+    type Error = Error;
+
+    fn try_from(response: crate::httpu::Response) -> Result<Self, Self::Error> {
+        let nts = header_traits::required::<String>(&response.headers, protocol::HEAD_NTS)?;
+        let notification_type =
+            header_traits::required::<String>(&response.headers, protocol::HEAD_NT)?;
+        let service_name = header_traits::required::<String>(&response.headers, protocol::HEAD_USN)?;
+
+        match nts.as_str() {
+            protocol::NTS_ALIVE => Ok(Advertisement::Alive {
+                notification_type,
+                service_name,
+                location: header_traits::required(&response.headers, protocol::HEAD_LOCATION)?,
+                max_age: header_traits::max_age(&response.headers, protocol::HEAD_CACHE_CONTROL)?,
+                boot_id: header_traits::with_default(&response.headers, protocol::HEAD_BOOTID),
+            }),
+            protocol::NTS_UPDATE => Ok(Advertisement::Update {
+                notification_type,
+                service_name,
+                location: header_traits::required(&response.headers, protocol::HEAD_LOCATION)?,
+                max_age: header_traits::max_age_opt(&response.headers, protocol::HEAD_CACHE_CONTROL)?,
+                boot_id: header_traits::with_default(&response.headers, protocol::HEAD_BOOTID),
+            }),
+            protocol::NTS_BYEBYE => Ok(Advertisement::ByeBye {
+                notification_type,
+                service_name,
+            }),
+            other => {
+                error!("Advertisement::try_from - unrecognised NTS value ({})", other);
+                Err(Error::MessageFormat(MessageErrorKind::InvalidFieldValue))
+            }
+        }
+    }
+}
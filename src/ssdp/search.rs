@@ -4,23 +4,37 @@ is sent out periodically and devices on the network can respond directly to the
 with their descriptions. With v1.1 of the SSDP specification a unicast search was added to
 send a request to a specific device.
 
-This module provides three functions that provide 1) multicast search, 2) unicast search, and 3)
-multicast search with caching. The caching version of search will merge the set of new responses
-with any (non-expired) previously cached responses.
+This module provides four functions that provide 1) multicast search, 2) unicast search, 3)
+multicast search with caching, and 4) an async, streaming multicast search. The caching version
+of search will merge the set of new responses with any (non-expired) previously cached
+responses, and the streaming version yields each [`Response`](struct.Response.html) as soon as
+it is received rather than waiting for the whole `max_wait_time` window to close.
 
 */
+use crate::ssdp::capture;
+use crate::ssdp::codec::SsdpCodec;
+use crate::ssdp::header_traits::{self, FromHeaders, ToHeaders};
 use crate::httpu::{
-    multicast, Options as MulticastOptions, RequestBuilder, Response as MulticastResponse,
+    multicast, Options as MulticastOptions, Request, RequestBuilder, Response as MulticastResponse,
 };
+use crate::ssdp::listen::Advertisement;
 use crate::ssdp::{protocol, ControlPoint};
 use crate::utils::{headers, user_agent};
 use crate::{Error, MessageErrorKind, SpecVersion};
+// The `FromHeaders`/`ToHeaders` derive macros (companion `rust-upnp-derive` crate) are
+// re-exported from the crate root under the same names as the traits they implement.
+use crate::{FromHeaders, ToHeaders};
+use futures::stream::{Stream, StreamExt};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{Display, Error as FmtError, Formatter};
-use std::net::SocketAddrV4;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio_util::udp::UdpFramed;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -32,6 +46,8 @@ use std::str::FromStr;
 /// This type does not separate out the version of a device or service type, it does ensure
 /// that the ':' separator character is present in the combined value.
 ///
+// `Serialize`/`Deserialize` (behind the `serde` feature) are implemented by hand further down
+// so that the wire representation is the canonical `ST` string, not a derived enum encoding.
 #[derive(Clone, Debug)]
 pub enum SearchTarget {
     /// Corresponds to the value `ssdp:all`
@@ -60,9 +76,13 @@ pub enum SearchTarget {
 /// The `Options::for_control_point` will set the control point as well as the version number.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Options {
     /// The specification that will be used to construct sent messages and to verify responses.
     /// Default: `SpecVersion:V10`.
+    // `SpecVersion` does not itself implement `Serialize`/`Deserialize`, so this goes through
+    // `spec_version_as_str` rather than requiring that of a type outside this module.
+    #[cfg_attr(feature = "serde", serde(with = "spec_version_as_str"))]
     pub spec_version: SpecVersion,
     /// The scope of the search to perform. Default: `SearchTarget::RootDevices`.
     pub search_target: SearchTarget,
@@ -80,7 +100,19 @@ pub struct Options {
     /// If specified this will be used to add certain control point values in the sent messages.
     /// This value is **only** used by the 2.0 specification where it is required, otherwise it
     /// will be ignores. Default: `None`.
+    // `ControlPoint` does not itself implement `Serialize`/`Deserialize`; omitted from the
+    // wire form rather than requiring that of a type outside this module. `Option::default()`
+    // is `None` regardless of `ControlPoint`, so `skip` needs no bound on it either.
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub control_point: Option<ControlPoint>,
+    /// If specified, every datagram sent or received by [`search_stream`](fn.search_stream.html)
+    /// and [`listen`](../listen/fn.listen.html) is appended to this path as a pcapng capture,
+    /// for sharing alongside an interop bug report instead of hand-transcribed logs. Opening
+    /// the file in Wireshark works directly. `search`/`search_once`/`search_once_to_device`
+    /// send through `httpu::multicast` rather than a socket this crate owns and so cannot
+    /// honor it; they return `Error::Unsupported` rather than silently dropping it. Default:
+    /// `None`.
+    pub capture_path: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug)]
@@ -97,15 +129,54 @@ pub struct ResponseCache {
     responses: Vec<CachedResponse>,
 }
 
-#[derive(Clone, Debug)]
+///
+/// The outbound M-SEARCH headers, built from an [`Options`](struct.Options.html) by
+/// [`build_m_search`] and pushed onto the wire via the derived `ToHeaders` impl instead of a
+/// hand-written `add_header` chain; used by both `search_once` and `build_request` so the two
+/// multicast send paths cannot drift apart.
+///
+#[derive(Clone, Debug, ToHeaders)]
+struct MSearchRequest {
+    #[header("HOST")]
+    host: String,
+    #[header("MAN")]
+    man: String,
+    #[header("MX")]
+    mx: String,
+    #[header("ST")]
+    search_target: String,
+    /// Added by the 1.1 specification.
+    #[header("USER-AGENT")]
+    user_agent: Option<String>,
+    /// Added by the 2.0 specification; `None` unless `Options::control_point` is set.
+    #[header("CPFN.UPNP.ORG")]
+    control_point_friendly_name: Option<String>,
+    /// Added by the 2.0 specification when `ControlPoint::port` is set.
+    #[header("CPPORT.UPNP.ORG")]
+    control_point_port: Option<String>,
+    /// Added by the 2.0 specification when `ControlPoint::uuid` is set.
+    #[header("CPUUID.UPNP.ORG")]
+    control_point_uuid: Option<String>,
+}
+
+#[derive(Clone, Debug, FromHeaders)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Response {
+    #[header("CACHE-CONTROL", required)]
     max_age: u64,
+    #[header("DATE", required)]
     date: String,
+    #[header("SERVER", required)]
     server: String,
+    #[header("LOCATION", required)]
     location: String,
+    #[header(skip)]
     search_target: SearchTarget,
+    #[header("USN", required)]
     service_name: String,
+    #[header("BOOTID.UPNP.ORG", required)]
     boot_id: u64,
+    #[header(rest)]
     other_headers: HashMap<String, String>,
 }
 
@@ -123,7 +194,15 @@ pub struct Response {
 pub fn search(options: Options) -> Result<ResponseCache, Error> {
     info!("search - options: {:?}", options);
     options.validate()?;
-    Err(Error::MessageFormat(MessageErrorKind::VersionMismatch))
+    reject_capture_path("search", &options)?;
+    let mut cache = ResponseCache {
+        options,
+        minimum_refresh: DEFAULT_MINIMUM_REFRESH,
+        last_updated: 0,
+        responses: Vec::new(),
+    };
+    cache.refresh()?;
+    Ok(cache)
 }
 
 ///
@@ -136,38 +215,10 @@ pub fn search(options: Options) -> Result<ResponseCache, Error> {
 pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
     info!("search_once - options: {:?}", options);
     options.validate()?;
+    reject_capture_path("search_once", &options)?;
+    let m_search = build_m_search(&options)?;
     let mut message_builder = RequestBuilder::new(protocol::METHOD_SEARCH);
-    // All headers from the original 1.0 specification.
-    message_builder
-        .add_header(protocol::HEAD_HOST, protocol::MULTICAST_ADDRESS)
-        .add_header(protocol::HEAD_MAN, protocol::HTTP_EXTENSION)
-        .add_header(protocol::HEAD_MX, &format!("{}", options.max_wait_time))
-        .add_header(protocol::HEAD_ST, &options.search_target.to_string());
-    // Headers added by 1.1 specification
-    if options.spec_version >= SpecVersion::V11 {
-        message_builder.add_header(
-            protocol::HEAD_USER_AGENT,
-            &user_agent::make(&options.spec_version, &options.product_and_version),
-        );
-    }
-    // Headers added by 2.0 specification
-    if options.spec_version >= SpecVersion::V20 {
-        match &options.control_point {
-            Some(cp) => {
-                message_builder.add_header(protocol::HEAD_CP_FN, &cp.friendly_name);
-                if let Some(port) = cp.port {
-                    message_builder.add_header(protocol::HEAD_TCP_PORT, &port.to_string());
-                }
-                if let Some(uuid) = &cp.uuid {
-                    message_builder.add_header(protocol::HEAD_TCP_PORT, &uuid);
-                }
-            }
-            None => {
-                error!("search_once - missing control point, required for UPnP/2.0");
-                return Err(Error::MessageFormat(MessageErrorKind::MissingRequiredField));
-            }
-        }
-    }
+    m_search.to_headers(&mut message_builder);
     trace!("search_once - {:?}", &message_builder);
     let raw_responses = multicast(
         &message_builder.into(),
@@ -198,6 +249,7 @@ pub fn search_once_to_device(
         options, device_address
     );
     options.validate()?;
+    reject_capture_path("search_once_to_device", &options)?;
     if options.spec_version >= SpecVersion::V11 {
         let mut message_builder = RequestBuilder::new(protocol::METHOD_SEARCH);
         message_builder
@@ -221,6 +273,161 @@ pub fn search_once_to_device(
     }
 }
 
+///
+/// Perform a multicast search exactly as `search_once` does, but return a stream that yields
+/// each [`Response`](struct.Response.html) the instant it is received rather than waiting for
+/// the whole `max_wait_time` window to close.
+///
+/// A device that answers to more than one `ST` (and so sends more than one datagram) is only
+/// yielded once; any reply carrying an already-seen `USN` header is silently dropped.
+///
+pub async fn search_stream(
+    options: Options,
+) -> Result<impl Stream<Item = Result<Response, Error>>, Error> {
+    info!("search_stream - options: {:?}", options);
+    options.validate()?;
+
+    let bind_ip = match &options.network_interface {
+        Some(name) => interface_address(name)?,
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+    let bind_address = SocketAddrV4::new(bind_ip, 0);
+    let socket = UdpSocket::bind(bind_address).await.map_err(Error::from)?;
+    socket.set_broadcast(true).map_err(Error::from)?;
+    let local_address = match socket.local_addr().map_err(Error::from)? {
+        std::net::SocketAddr::V4(address) => address,
+        std::net::SocketAddr::V6(_) => bind_address,
+    };
+
+    let mut capture = match &options.capture_path {
+        Some(path) => Some(capture::Capture::open(path).map_err(Error::from)?),
+        None => None,
+    };
+
+    let request = build_request(&options)?;
+    let destination: SocketAddrV4 = protocol::MULTICAST_ADDRESS.parse().unwrap();
+    trace!("search_stream - sending {} bytes to {}", request.len(), destination);
+    if let Some(capture) = capture.as_mut() {
+        capture
+            .write_datagram(local_address, destination, &request)
+            .map_err(Error::from)?;
+    }
+    socket
+        .send_to(&request, destination)
+        .await
+        .map_err(Error::from)?;
+
+    let deadline = tokio::time::sleep(Duration::from_secs(options.max_wait_time as u64));
+    let framed = UdpFramed::new(socket, SsdpCodec::default());
+
+    let mut seen = HashSet::new();
+    Ok(framed
+        .take_until(deadline)
+        .filter_map(move |frame| {
+            let parsed = frame.map_err(Error::from).and_then(|(raw, from)| {
+                if let Some(capture) = capture.as_mut() {
+                    if let std::net::SocketAddr::V4(from) = from {
+                        let rendered = capture::render_headers("HTTP/1.1 200 OK", &raw.headers);
+                        let _ = capture.write_datagram(from, local_address, &rendered);
+                    }
+                }
+                let response: Response = raw.try_into()?;
+                Ok(response)
+            });
+            let keep = match &parsed {
+                Ok(response) => seen.insert(response.service_name.clone()),
+                Err(_) => true,
+            };
+            async move { if keep { Some(parsed) } else { None } }
+        }))
+}
+
+/// Reject `options.capture_path` up front for the blocking search functions (`search`,
+/// `search_once`, `search_once_to_device`), which send through `httpu::multicast` and so have
+/// no socket of their own to capture from; silently ignoring the option would leave a caller
+/// who set it believing their bug report capture has packets in it when it does not. Only
+/// `search_stream`/`listen` honor `capture_path`.
+fn reject_capture_path(caller: &str, options: &Options) -> Result<(), Error> {
+    if options.capture_path.is_some() {
+        error!(
+            "{} - capture_path is not supported here; use search_stream instead",
+            caller
+        );
+        return Err(Error::Unsupported);
+    }
+    Ok(())
+}
+
+/// Build the raw M-SEARCH datagram for `options`; shares the same [`MSearchRequest`] headers
+/// `search_once` sends, so the blocking and streaming search paths cannot drift apart.
+fn build_request(options: &Options) -> Result<Vec<u8>, Error> {
+    let m_search = build_m_search(options)?;
+    let mut message_builder = RequestBuilder::new(protocol::METHOD_SEARCH);
+    m_search.to_headers(&mut message_builder);
+    // Same conversion `search_once`/`search_once_to_device` hand to `multicast`; `Request`
+    // knows how to render itself onto the wire, `RequestBuilder` does not.
+    let request: Request = message_builder.into();
+    Ok(request.into())
+}
+
+/// Build the [`MSearchRequest`] headers for a multicast M-SEARCH from `options`, applying the
+/// same per-version rules `search_once` and `build_request` previously implemented by hand:
+/// `USER-AGENT` from 1.1 onward, and the `control_point` fields (required) from 2.0 onward.
+fn build_m_search(options: &Options) -> Result<MSearchRequest, Error> {
+    let user_agent = if options.spec_version >= SpecVersion::V11 {
+        Some(user_agent::make(&options.spec_version, &options.product_and_version))
+    } else {
+        None
+    };
+
+    let (control_point_friendly_name, control_point_port, control_point_uuid) =
+        if options.spec_version >= SpecVersion::V20 {
+            match &options.control_point {
+                Some(cp) => (
+                    Some(cp.friendly_name.clone()),
+                    cp.port.map(|port| port.to_string()),
+                    cp.uuid.clone(),
+                ),
+                None => {
+                    error!("build_m_search - missing control point, required for UPnP/2.0");
+                    return Err(Error::MessageFormat(MessageErrorKind::MissingRequiredField));
+                }
+            }
+        } else {
+            (None, None, None)
+        };
+
+    Ok(MSearchRequest {
+        host: protocol::MULTICAST_ADDRESS.to_string(),
+        man: protocol::HTTP_EXTENSION.to_string(),
+        mx: options.max_wait_time.to_string(),
+        search_target: options.search_target.to_string(),
+        user_agent,
+        control_point_friendly_name,
+        control_point_port,
+        control_point_uuid,
+    })
+}
+
+/// Resolve `name` to the IPv4 address `search_stream` should bind to, mirroring what
+/// `multicast` does internally for the blocking search functions via
+/// `MulticastOptions::network_interface`. Also used by [`listen`](../listen/fn.listen.html),
+/// which binds its own socket the same way.
+pub(crate) fn interface_address(name: &str) -> Result<Ipv4Addr, Error> {
+    if_addrs::get_if_addrs()
+        .map_err(Error::from)?
+        .into_iter()
+        .find(|interface| &interface.name == name)
+        .and_then(|interface| match interface.ip() {
+            std::net::IpAddr::V4(address) => Some(address),
+            std::net::IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| {
+            error!("interface_address - no IPv4 address for interface {}", name);
+            Error::MessageFormat(MessageErrorKind::InvalidFieldValue)
+        })
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -231,7 +438,7 @@ impl Display for SearchTarget {
             f,
             "{}",
             match self {
-                SearchTarget::All => "ssdp::all".to_string(),
+                SearchTarget::All => "ssdp:all".to_string(),
                 SearchTarget::RootDevices => "upnp:rootdevice".to_string(),
                 SearchTarget::Device(device) => format!("uuid:{}", device),
                 SearchTarget::DeviceType(device) =>
@@ -251,7 +458,7 @@ impl FromStr for SearchTarget {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == "ssdp::all" {
+        if s == "ssdp:all" {
             Ok(SearchTarget::All)
         } else if s == "upnp:rootdevice" {
             Ok(SearchTarget::RootDevices)
@@ -261,13 +468,96 @@ impl FromStr for SearchTarget {
             Ok(SearchTarget::DeviceType(s[28..].to_string()))
         } else if s.starts_with("urn:schemas-upnp-org:service:") {
             Ok(SearchTarget::ServiceType(s[29..].to_string()))
-        // TODO: domain devices and services
+        } else if let Some(rest) = s.strip_prefix("urn:") {
+            if let Some((domain, device)) = rest.split_once(":device:") {
+                Ok(SearchTarget::DomainDeviceType(
+                    domain.to_string(),
+                    device.to_string(),
+                ))
+            } else if let Some((domain, service)) = rest.split_once(":service:") {
+                Ok(SearchTarget::DomainServiceType(
+                    domain.to_string(),
+                    service.to_string(),
+                ))
+            } else {
+                Err(())
+            }
         } else {
             Err(())
         }
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for SearchTarget {
+    // Serialize to the canonical `ST` string form (`upnp:rootdevice`,
+    // `urn:schemas-upnp-org:device:...`, ...) rather than a derived enum encoding, so the JSON
+    // representation is the same string a caller would see on the wire.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SearchTarget {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid search target: {}", value)))
+    }
+}
+
+/// `serde(with = "...")` helper for `Options::spec_version`, since `SpecVersion` itself does
+/// not implement `Serialize`/`Deserialize`.
+#[cfg(feature = "serde")]
+mod spec_version_as_str {
+    use crate::SpecVersion;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &SpecVersion, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            SpecVersion::V10 => "1.0",
+            SpecVersion::V11 => "1.1",
+            SpecVersion::V20 => "2.0",
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SpecVersion, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        match value.as_str() {
+            "1.0" => Ok(SpecVersion::V10),
+            "1.1" => Ok(SpecVersion::V11),
+            "2.0" => Ok(SpecVersion::V20),
+            other => Err(serde::de::Error::custom(format!(
+                "invalid spec version: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl Default for SearchTarget {
+    // `Response::search_target` is not yet recovered from the `ST` header (see the comment
+    // on its `TryFrom<MulticastResponse>` impl), so `#[header(skip)]` falls back to this.
+    fn default() -> Self {
+        SearchTarget::All
+    }
+}
+
 impl Default for Options {
     fn default() -> Self {
         Options {
@@ -277,6 +567,7 @@ impl Default for Options {
             max_wait_time: 2,
             product_and_version: None,
             control_point: None,
+            capture_path: None,
         }
     }
 }
@@ -336,83 +627,233 @@ impl From<Options> for MulticastOptions {
         let mut multicast_options = MulticastOptions::default();
         multicast_options.network_interface = options.network_interface;
         multicast_options.timeout = options.max_wait_time as u64;
+        // `httpu::Options` has no `capture_path` of its own; the blocking functions this type
+        // feeds reject a set `capture_path` before ever reaching here (see
+        // `reject_capture_path`), so there is nothing to carry across.
         multicast_options
     }
 }
 
-const REQUIRED_HEADERS: [&str; 7] = [
-    protocol::HEAD_BOOTID,
-    protocol::HEAD_CACHE_CONTROL,
-    protocol::HEAD_DATE,
-    protocol::HEAD_EXT,
-    protocol::HEAD_LOCATION,
-    protocol::HEAD_ST,
-    protocol::HEAD_USN,
-];
-
 impl TryFrom<MulticastResponse> for Response {
     type Error = Error;
 
+    // `EXT` and `ST` are required on every reply but neither is mapped onto a `Response`
+    // field directly (`EXT` must simply be present and empty; `search_target` is not yet
+    // recovered from `ST`, see `SearchTarget::default`), so they are checked here before
+    // handing the rest of the header map to the generated `FromHeaders` impl.
     fn try_from(response: MulticastResponse) -> Result<Self, Self::Error> {
-        headers::check_required(&response.headers, &REQUIRED_HEADERS)?;
-        headers::check_empty(
-            response.headers.get(protocol::HEAD_EXT).unwrap(),
-            protocol::HEAD_EXT,
-        )?;
-
-        let remaining_headers: HashMap<String, String> = response
-            .headers
-            .clone()
-            .iter()
-            .filter(|(k, _)| REQUIRED_HEADERS.contains(&k.as_str()))
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-
-        Ok(Response {
-            boot_id: headers::check_parsed_value::<u64>(
-                response.headers.get(protocol::HEAD_BOOTID).unwrap(),
-                protocol::HEAD_BOOTID,
-            )?,
-            max_age: headers::check_parsed_value::<u64>(
-                &headers::check_regex(
-                    response.headers.get(protocol::HEAD_CACHE_CONTROL).unwrap(),
-                    protocol::HEAD_CACHE_CONTROL,
-                    &Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap(),
-                )?,
-                protocol::HEAD_CACHE_CONTROL,
-            )?,
-            date: headers::check_not_empty(
-                response.headers.get(protocol::HEAD_DATE).unwrap(),
-                protocol::HEAD_DATE,
-            )?,
-            server: headers::check_not_empty(
-                response.headers.get(protocol::HEAD_SERVER).unwrap(),
-                protocol::HEAD_SERVER,
-            )?,
-            location: headers::check_not_empty(
-                response.headers.get(protocol::HEAD_LOCATION).unwrap(),
-                protocol::HEAD_LOCATION,
-            )?,
-            search_target: SearchTarget::All,
-            service_name: headers::check_not_empty(
-                response.headers.get(protocol::HEAD_USN).unwrap(),
-                protocol::HEAD_USN,
-            )?,
-            other_headers: remaining_headers,
-        })
+        let ext = header_traits::required::<String>(&response.headers, protocol::HEAD_EXT)?;
+        headers::check_empty(&ext, protocol::HEAD_EXT)?;
+        header_traits::required::<String>(&response.headers, protocol::HEAD_ST)?;
+        let mut response = Response::from_headers(&response.headers)?;
+        // Neither is mapped onto a field, so the generated `#[header(rest)]` does not know
+        // they are claimed; without this they would leak into `other_headers` even though
+        // they were already handled above.
+        response.other_headers.remove(protocol::HEAD_EXT);
+        response.other_headers.remove(protocol::HEAD_ST);
+        Ok(response)
+    }
+}
+
+impl Response {
+    /// The `max-age` component of the reply's `CACHE-CONTROL` header, in seconds.
+    pub fn max_age(&self) -> u64 {
+        self.max_age
+    }
+
+    /// The reply's `DATE` header.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// The reply's `SERVER` header.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// The reply's `LOCATION` header, the URL of the device/service description.
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// The search target of this reply. Always `SearchTarget::All`: the reply's `ST` header is
+    /// checked for presence but not parsed into this value.
+    pub fn search_target(&self) -> &SearchTarget {
+        &self.search_target
+    }
+
+    /// The reply's `USN` header, identifying the specific device or service that replied.
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    /// The reply's `BOOTID.UPNP.ORG` header; a later reply with a higher value means the
+    /// device has rebooted.
+    pub fn boot_id(&self) -> u64 {
+        self.boot_id
+    }
+
+    /// Every header on the reply that is not already exposed through one of the accessors
+    /// above, other than `EXT` and `ST`, which are checked on receipt and then dropped.
+    pub fn other_headers(&self) -> &HashMap<String, String> {
+        &self.other_headers
+    }
+}
+
+/// The default `minimum_refresh`, in seconds, used by `search()`; chosen to comfortably
+/// exceed the default `max_wait_time` so a caller polling in a tight loop does not flood the
+/// network with M-SEARCH traffic.
+const DEFAULT_MINIMUM_REFRESH: u16 = 30;
+
+/// Seconds since the Unix epoch, used to compute and check `CachedResponse::expiration`.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The device-identifying portion of a `USN`, e.g. `uuid:ABC::upnp:rootdevice` and
+/// `uuid:ABC::urn:schemas-upnp-org:service:Foo:1` both yield `ABC`. Used to find every cached
+/// sub-device/service entry belonging to a device that has rebooted.
+fn device_uuid(service_name: &str) -> &str {
+    match service_name.strip_prefix("uuid:") {
+        Some(rest) => rest.split("::").next().unwrap_or(rest),
+        None => service_name,
     }
 }
 
 impl ResponseCache {
-    pub fn refresh(&mut self) -> Self {
-        self.to_owned()
+    /// Re-run `search_once` with the options this cache was created with, merge the results
+    /// in, and drop any entry that has since expired; returns the resulting set, same as
+    /// `responses()`.
+    ///
+    /// Honors `minimum_refresh`: if called again before that many seconds have passed since
+    /// `last_updated`, this is a no-op that returns the already-cached set without touching
+    /// the network.
+    ///
+    /// A device/service entry is normally merged by `USN`: the existing entry is replaced with
+    /// the new response and its expiration. However if a response's `BOOTID.UPNP.ORG` is
+    /// higher than the highest previously seen for that device, the device is treated as
+    /// rebooted: every cached entry for that device (including sub-devices/services, which
+    /// share the same UUID) is dropped rather than merged, since a reboot can change far more
+    /// than the header that changed.
+    pub fn refresh(&mut self) -> Result<Vec<Response>, Error> {
+        let now = now();
+        if self.last_updated != 0 && now.saturating_sub(self.last_updated) < self.minimum_refresh as u64 {
+            trace!("refresh - called within minimum_refresh, returning cached responses");
+            return Ok(self.responses());
+        }
+
+        let new_responses = search_once(self.options.clone())?;
+
+        let mut max_boot_id: HashMap<String, u64> = HashMap::new();
+        for cached in &self.responses {
+            let entry = max_boot_id
+                .entry(device_uuid(&cached.response.service_name).to_string())
+                .or_insert(0);
+            *entry = (*entry).max(cached.response.boot_id);
+        }
+
+        for response in new_responses {
+            let uuid = device_uuid(&response.service_name).to_string();
+            // Only a device we had already cached a `boot_id` for can be "rebooted"; on a
+            // cold start `max_boot_id` is empty and every device is simply new.
+            let rebooted = match max_boot_id.get(&uuid) {
+                Some(previous) => response.boot_id > *previous,
+                None => false,
+            };
+            if rebooted {
+                info!(
+                    "refresh - device {} rebooted (boot_id {}), replacing its cached entries",
+                    uuid, response.boot_id
+                );
+                self.responses
+                    .retain(|cached| device_uuid(&cached.response.service_name) != uuid);
+                max_boot_id.insert(uuid, response.boot_id);
+            } else {
+                self.responses
+                    .retain(|cached| cached.response.service_name != response.service_name);
+            }
+            self.responses.push(CachedResponse {
+                expiration: now + response.max_age,
+                response,
+            });
+        }
+
+        self.responses.retain(|cached| cached.expiration > now);
+        self.last_updated = now;
+        Ok(self.responses())
     }
 
-    pub fn last_updated(self) -> u64 {
+    /// Fold a single advertisement from [`listen`](../listen/fn.listen.html)'s stream into
+    /// this cache, so alive/update advertisements refresh an entry and byebye advertisements
+    /// remove one in between calls to `refresh()`.
+    ///
+    /// An alive/update for a device not already in the cache is ignored rather than
+    /// synthesized into a partial `Response`, since `NOTIFY` does not carry every header
+    /// `Response` requires (e.g. `SERVER`, `DATE`); it will be picked up on the next
+    /// `refresh()`.
+    pub fn apply_advertisement(&mut self, advertisement: Advertisement) {
+        match advertisement {
+            Advertisement::Alive {
+                service_name,
+                max_age,
+                boot_id,
+                ..
+            } => self.apply_alive_or_update(service_name, Some(max_age), boot_id),
+            Advertisement::Update {
+                service_name,
+                max_age,
+                boot_id,
+                ..
+            } => self.apply_alive_or_update(service_name, max_age, boot_id),
+            Advertisement::ByeBye { service_name, .. } => {
+                self.responses
+                    .retain(|cached| cached.response.service_name != service_name);
+            }
+        }
+    }
+
+    /// Shared `Alive`/`Update` handling for `apply_advertisement`: refresh the cached entry's
+    /// expiration when the advertisement carried a `max_age`, and its `boot_id` when the
+    /// advertisement carried one. `boot_id == 0` is `with_default`'s "header absent" value, not
+    /// a real `BOOTID.UPNP.ORG` of 0, so it must not overwrite a real cached `boot_id` — doing
+    /// so would make the next `refresh()` see the device's true `boot_id` as higher than the
+    /// (wrongly zeroed) cached one and spuriously treat it as rebooted.
+    fn apply_alive_or_update(&mut self, service_name: String, max_age: Option<u64>, boot_id: u64) {
+        match self
+            .responses
+            .iter_mut()
+            .find(|cached| cached.response.service_name == service_name)
+        {
+            Some(cached) => {
+                if boot_id != 0 {
+                    cached.response.boot_id = boot_id;
+                }
+                if let Some(max_age) = max_age {
+                    cached.expiration = now() + max_age;
+                }
+            }
+            None => trace!(
+                "apply_advertisement - {} advertised but not yet cached, deferring to next refresh",
+                service_name
+            ),
+        }
+    }
+
+    pub fn last_updated(&self) -> u64 {
         self.last_updated
     }
 
+    /// The non-expired cached responses, merging the results of past `refresh()` calls (and
+    /// any applied `NOTIFY` advertisements) with whatever the most recent one returned.
     pub fn responses(&self) -> Vec<Response> {
-        Vec::new()
+        let now = now();
+        self.responses
+            .iter()
+            .filter(|cached| cached.expiration > now)
+            .map(|cached| cached.response.clone())
+            .collect()
     }
 }